@@ -5,10 +5,11 @@
 use std::fs::File;
 use std::io;
 use std::mem;
-use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::result;
 
 use libc::{c_int, c_void, dup, eventfd, poll, pollfd, read, write, POLLIN};
+pub use libc::{EFD_CLOEXEC, EFD_NONBLOCK, EFD_SEMAPHORE};
 
 /// A safe wrapper around a Linux eventfd (man 2 eventfd).
 ///
@@ -21,9 +22,20 @@ pub struct EventFd {
 impl EventFd {
     /// Creates a new blocking EventFd with an initial value of 0.
     pub fn new() -> result::Result<EventFd, io::Error> {
+        EventFd::with_flags(0)
+    }
+
+    /// Creates a new EventFd with the given combination of `EFD_CLOEXEC`, `EFD_NONBLOCK` and
+    /// `EFD_SEMAPHORE` flags and an initial value of 0.
+    ///
+    /// When `EFD_SEMAPHORE` is set, each `read()` decrements the counter by exactly 1 and
+    /// returns 1, rather than draining the whole count to zero, so the eventfd behaves like a
+    /// counting semaphore. When `EFD_NONBLOCK` is set, `read()`/`write()` return `EAGAIN` instead
+    /// of blocking.
+    pub fn with_flags(flags: i32) -> result::Result<EventFd, io::Error> {
         // This is safe because eventfd merely allocated an eventfd for our process and we handle
         // the error case.
-        let ret = unsafe { eventfd(0, 0) };
+        let ret = unsafe { eventfd(0, flags) };
         if ret < 0 {
             Err(io::Error::last_os_error())
         } else {
@@ -91,6 +103,19 @@ impl EventFd {
         }
     }
 
+    /// Adopts `file` as an EventFd without checking that it actually wraps an eventfd. The
+    /// caller is responsible for having received `file` as, e.g., an eventfd sent across a Unix
+    /// socket to a jailed child process.
+    pub fn from_file(file: File) -> EventFd {
+        EventFd { eventfd: file }
+    }
+
+    /// Converts this EventFd back into the `File` it wraps, relinquishing the `EventFd` API but
+    /// keeping the fd open.
+    pub fn into_file(self) -> File {
+        self.eventfd
+    }
+
     /// Clones this EventFd, internally creating a new file descriptor. The new EventFd will share
     /// the same underlying count within the kernel.
     pub fn try_clone(&self) -> result::Result<EventFd, io::Error> {
@@ -114,6 +139,27 @@ impl AsRawFd for EventFd {
     }
 }
 
+impl IntoRawFd for EventFd {
+    fn into_raw_fd(self) -> RawFd {
+        self.eventfd.into_raw_fd()
+    }
+}
+
+impl FromRawFd for EventFd {
+    /// Constructs an EventFd from a raw fd previously returned by `into_raw_fd`, e.g. one
+    /// rebuilt after being passed across a process boundary over a Unix socket.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be a valid, open eventfd that is not owned by anything else, since this takes
+    /// ownership of it and will close it on drop.
+    unsafe fn from_raw_fd(fd: RawFd) -> EventFd {
+        EventFd {
+            eventfd: File::from_raw_fd(fd),
+        }
+    }
+}
+
 #[derive(Debug)]
 /// Describes the errors that may occur while trying to work with EventFds
 pub enum Error {
@@ -157,6 +203,32 @@ mod tests {
         assert_eq!(r, 1189998819999197253);
     }
 
+    #[test]
+    fn semaphore() {
+        let evt = EventFd::with_flags(EFD_SEMAPHORE).unwrap();
+        evt.write(2).unwrap();
+        assert_eq!(evt.read().unwrap(), 1);
+        assert_eq!(evt.read().unwrap(), 1);
+    }
+
+    #[test]
+    fn into_from_raw_fd() {
+        let evt = EventFd::new().unwrap();
+        evt.write(55).unwrap();
+        let fd = evt.into_raw_fd();
+        let evt = unsafe { EventFd::from_raw_fd(fd) };
+        assert_eq!(evt.read().unwrap(), 55);
+    }
+
+    #[test]
+    fn from_into_file() {
+        let evt = EventFd::new().unwrap();
+        evt.write(42).unwrap();
+        let file = evt.into_file();
+        let evt = EventFd::from_file(file);
+        assert_eq!(evt.read().unwrap(), 42);
+    }
+
     #[test]
     fn clone() {
         let evt = EventFd::new().unwrap();
@@ -0,0 +1,18 @@
+// Copyright 2017 The Chromium OS Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the THIRD-PARTY file.
+
+//! Small safe wrappers around Linux kernel primitives (eventfd, epoll, ...) that are used
+//! throughout the VMM to signal, wait on, and multiplex file descriptors.
+
+extern crate libc;
+
+pub mod eventfd;
+pub mod poll;
+pub mod signalfd;
+pub mod timerfd;
+
+pub use eventfd::{EventFd, EFD_CLOEXEC, EFD_NONBLOCK, EFD_SEMAPHORE};
+pub use poll::{PollContext, PollEvent, PollEvents};
+pub use signalfd::SignalFd;
+pub use timerfd::TimerFd;
@@ -0,0 +1,259 @@
+// Copyright 2017 The Chromium OS Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the THIRD-PARTY file.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::ptr::null_mut;
+use std::result;
+
+use libc::{
+    c_int, epoll_create1, epoll_ctl, epoll_event, epoll_wait, EPOLLHUP, EPOLLIN, EPOLLOUT,
+    EPOLL_CLOEXEC, EPOLL_CTL_ADD, EPOLL_CTL_DEL,
+};
+
+/// The maximum number of ready events `PollContext::wait` will return in a single call.
+const POLL_CONTEXT_MAX_EVENTS: usize = 1024;
+
+/// An error that occurred while using a `PollContext`.
+#[derive(Debug)]
+pub enum Error {
+    /// Creating the underlying epoll fd via `epoll_create1` failed.
+    CreatePollContext(io::Error),
+    /// Registering an fd with `epoll_ctl` failed.
+    PollContextAdd(io::Error),
+    /// Unregistering an fd with `epoll_ctl` failed.
+    PollContextDelete(io::Error),
+    /// Waiting for events via `epoll_wait` failed.
+    PollContextWait(io::Error),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// A single readied event returned by `PollContext::wait`, pairing the token that was given to
+/// `add` with the kind of readiness the kernel reported.
+#[derive(Copy, Clone)]
+pub struct PollEvent<T> {
+    token: T,
+    events: u32,
+}
+
+impl<T: Copy> PollEvent<T> {
+    /// The token associated with the fd that triggered this event.
+    pub fn token(&self) -> T {
+        self.token
+    }
+
+    /// True if the fd has data available to read.
+    pub fn readable(&self) -> bool {
+        self.events & (EPOLLIN as u32) != 0
+    }
+
+    /// True if the fd is ready to accept a write.
+    pub fn writable(&self) -> bool {
+        self.events & (EPOLLOUT as u32) != 0
+    }
+
+    /// True if the other end of the fd has hung up.
+    pub fn hungup(&self) -> bool {
+        self.events & (EPOLLHUP as u32) != 0
+    }
+}
+
+/// An iterator over the events returned by a single `PollContext::wait` call.
+pub struct PollEvents<'a, T: 'a> {
+    context: &'a PollContext<T>,
+    count: usize,
+    pos: usize,
+}
+
+impl<'a, T: Copy> Iterator for PollEvents<'a, T> {
+    type Item = PollEvent<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.count {
+            return None;
+        }
+        let raw_event = self.context.events.borrow()[self.pos];
+        self.pos += 1;
+
+        let token = *self
+            .context
+            .tokens
+            .borrow()
+            .get(&(raw_event.u64 as RawFd))
+            .expect("PollContext returned an event for an fd that was never registered");
+        Some(PollEvent {
+            token,
+            events: raw_event.events,
+        })
+    }
+}
+
+/// A wrapper around Linux's epoll API (man 7 epoll) that lets callers wait on many file
+/// descriptors at once, each tagged with a caller-chosen token of type `T`, rather than
+/// dedicating a blocking `read()` (or a thread) to every individual fd.
+pub struct PollContext<T> {
+    epoll_ctx: File,
+    // Tokens for the fds currently registered with `epoll_ctx`, keyed by raw fd since that is
+    // all `epoll_wait` hands back to us in `epoll_event.u64`.
+    tokens: RefCell<HashMap<RawFd, T>>,
+    events: RefCell<Vec<epoll_event>>,
+}
+
+impl<T: Copy> PollContext<T> {
+    /// Creates a new `PollContext`.
+    pub fn new() -> Result<PollContext<T>> {
+        // This is safe because we check the return value for an error.
+        let ret = unsafe { epoll_create1(EPOLL_CLOEXEC) };
+        if ret < 0 {
+            return Err(Error::CreatePollContext(io::Error::last_os_error()));
+        }
+        // This is safe because we checked ret for success and know the kernel gave us an fd that
+        // we own.
+        let epoll_ctx = unsafe { File::from_raw_fd(ret) };
+
+        Ok(PollContext {
+            epoll_ctx,
+            tokens: RefCell::new(HashMap::new()),
+            events: RefCell::new(vec![unsafe { mem::zeroed() }; POLL_CONTEXT_MAX_EVENTS]),
+        })
+    }
+
+    /// Registers `fd` and associates `token` with it, so that `token` will be returned by `wait`
+    /// whenever `fd` becomes readable, writable, or hangs up.
+    pub fn add(&self, fd: &dyn AsRawFd, token: T) -> Result<()> {
+        let raw_fd = fd.as_raw_fd();
+        let mut event = epoll_event {
+            events: (EPOLLIN | EPOLLOUT) as u32,
+            u64: raw_fd as u64,
+        };
+        // This is safe because we give a valid epoll fd and fd, and check the return value.
+        let ret = unsafe {
+            epoll_ctl(
+                self.epoll_ctx.as_raw_fd(),
+                EPOLL_CTL_ADD,
+                raw_fd,
+                &mut event,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::PollContextAdd(io::Error::last_os_error()));
+        }
+        self.tokens.borrow_mut().insert(raw_fd, token);
+        Ok(())
+    }
+
+    /// Unregisters `fd` so that it is no longer considered by `wait`.
+    pub fn delete(&self, fd: &dyn AsRawFd) -> Result<()> {
+        let raw_fd = fd.as_raw_fd();
+        // This is safe because we give a valid epoll fd and fd, and check the return value. The
+        // event argument is ignored by EPOLL_CTL_DEL, so we pass null; kernels older than 2.6.9
+        // reject a null event pointer here.
+        let ret = unsafe {
+            epoll_ctl(
+                self.epoll_ctx.as_raw_fd(),
+                EPOLL_CTL_DEL,
+                raw_fd,
+                null_mut(),
+            )
+        };
+        if ret < 0 {
+            return Err(Error::PollContextDelete(io::Error::last_os_error()));
+        }
+        self.tokens.borrow_mut().remove(&raw_fd);
+        Ok(())
+    }
+
+    /// Blocks until one or more of the registered fds is readable, writable, or has hung up,
+    /// then returns an iterator over the tokens of the fds that are ready.
+    pub fn wait(&self) -> Result<PollEvents<'_, T>> {
+        let mut events = self.events.borrow_mut();
+        let max_events = events.len() as c_int;
+        let ret = loop {
+            // This is safe because we give a valid epoll fd and buffer and check the return
+            // value. We retry on EINTR rather than surfacing a spurious wakeup to the caller.
+            let ret = unsafe {
+                epoll_wait(
+                    self.epoll_ctx.as_raw_fd(),
+                    events.as_mut_ptr(),
+                    max_events,
+                    -1,
+                )
+            };
+            if ret >= 0 {
+                break ret;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(Error::PollContextWait(err));
+            }
+        };
+        drop(events);
+
+        Ok(PollEvents {
+            context: self,
+            count: ret as usize,
+            pos: 0,
+        })
+    }
+}
+
+impl<T> AsRawFd for PollContext<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll_ctx.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eventfd::EventFd;
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    enum Token {
+        One,
+        Two,
+    }
+
+    #[test]
+    fn new() {
+        PollContext::<Token>::new().unwrap();
+    }
+
+    #[test]
+    fn add_and_wait() {
+        let ctx = PollContext::new().unwrap();
+        let evt1 = EventFd::new().unwrap();
+        let evt2 = EventFd::new().unwrap();
+        ctx.add(&evt1, Token::One).unwrap();
+        ctx.add(&evt2, Token::Two).unwrap();
+
+        evt2.write(1).unwrap();
+
+        // Both eventfds are always writable (neither counter is anywhere near overflow), so both
+        // are readied by epoll_wait; only evt2 is also readable, since only it was written to.
+        let events: Vec<_> = ctx.wait().unwrap().collect();
+        assert_eq!(events.len(), 2);
+
+        let one = events.iter().find(|e| e.token() == Token::One).unwrap();
+        assert!(!one.readable());
+        assert!(one.writable());
+
+        let two = events.iter().find(|e| e.token() == Token::Two).unwrap();
+        assert!(two.readable());
+        assert!(two.writable());
+    }
+
+    #[test]
+    fn delete() {
+        let ctx = PollContext::new().unwrap();
+        let evt = EventFd::new().unwrap();
+        ctx.add(&evt, Token::One).unwrap();
+        ctx.delete(&evt).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2017 The Chromium OS Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the THIRD-PARTY file.
+
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::result;
+
+use libc::{
+    c_int, c_void, pthread_sigmask, read, sigaddset, sigemptyset, signalfd, signalfd_siginfo,
+    sigset_t, SFD_CLOEXEC, SFD_NONBLOCK, SIG_BLOCK, SIG_SETMASK,
+};
+
+/// A safe wrapper around a Linux signalfd (man 2 signalfd).
+///
+/// A signalfd lets a blocked POSIX signal be consumed via a plain `read()` instead of an async
+/// signal handler, so it can be registered like any other fd in a `PollContext` and handled
+/// synchronously in the main event loop, without the reentrancy hazards of a signal handler.
+pub struct SignalFd {
+    signalfd: File,
+    // The thread's signal mask from before `signal` was blocked, so `Drop` can restore it
+    // exactly rather than assuming `signal` was unblocked beforehand.
+    old_mask: sigset_t,
+}
+
+fn sigset_with(signal: c_int) -> result::Result<sigset_t, io::Error> {
+    // This is safe because we give a valid, properly aligned pointer to a stack-allocated
+    // sigset_t and check the return value.
+    unsafe {
+        let mut mask: sigset_t = mem::zeroed();
+        if sigemptyset(&mut mask) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if sigaddset(&mut mask, signal) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(mask)
+    }
+}
+
+impl SignalFd {
+    /// Blocks `signal` for the calling thread and creates a non-blocking `SignalFd` that reads
+    /// its occurrences.
+    pub fn new(signal: c_int) -> result::Result<SignalFd, io::Error> {
+        let mask = sigset_with(signal)?;
+
+        // This is safe because we give valid mask and oldset pointers and check the return
+        // value. Blocking the signal keeps the kernel from also delivering it via the default
+        // disposition or an installed signal handler. `pthread_sigmask` returns the error number
+        // directly rather than setting errno, so we build the `io::Error` from it ourselves.
+        let mut old_mask: sigset_t = unsafe { mem::zeroed() };
+        let ret = unsafe { pthread_sigmask(SIG_BLOCK, &mask, &mut old_mask) };
+        if ret != 0 {
+            return Err(io::Error::from_raw_os_error(ret));
+        }
+
+        // This is safe because we give a valid mask and check the return value.
+        let ret = unsafe { signalfd(-1, &mask, SFD_NONBLOCK | SFD_CLOEXEC) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // This is safe because we checked ret for success and know the kernel gave us an fd that
+        // we own.
+        Ok(SignalFd {
+            signalfd: unsafe { File::from_raw_fd(ret) },
+            old_mask,
+        })
+    }
+
+    /// Reads the next occurrence of the signal this fd was created for, or `None` if none is
+    /// currently pending.
+    pub fn read(&self) -> result::Result<Option<signalfd_siginfo>, io::Error> {
+        let mut siginfo: signalfd_siginfo = unsafe { mem::zeroed() };
+        // This is safe because we made this fd and the pointer we pass can not overflow because
+        // we give the syscall's size parameter properly.
+        let ret = unsafe {
+            read(
+                self.as_raw_fd(),
+                &mut siginfo as *mut signalfd_siginfo as *mut c_void,
+                mem::size_of::<signalfd_siginfo>(),
+            )
+        };
+        if ret == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::WouldBlock {
+                Ok(None)
+            } else {
+                Err(err)
+            }
+        } else {
+            Ok(Some(siginfo))
+        }
+    }
+}
+
+impl AsRawFd for SignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.signalfd.as_raw_fd()
+    }
+}
+
+impl Drop for SignalFd {
+    fn drop(&mut self) {
+        // This is safe because `old_mask` is a valid mask captured from the thread's own state
+        // in `new`. We ignore the result because there is nothing we can do about a failure to
+        // restore the mask here.
+        unsafe {
+            pthread_sigmask(SIG_SETMASK, &self.old_mask, std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc::SIGUSR1;
+
+    #[test]
+    fn new() {
+        SignalFd::new(SIGUSR1).unwrap();
+    }
+
+    #[test]
+    fn read_nothing() {
+        let sigfd = SignalFd::new(SIGUSR1).unwrap();
+        assert!(sigfd.read().unwrap().is_none());
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2017 The Chromium OS Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the THIRD-PARTY file.
+
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::result;
+use std::time::Duration;
+
+use libc::{
+    c_void, itimerspec, poll, pollfd, read, timerfd_create, timerfd_settime, timespec,
+    CLOCK_MONOTONIC, POLLIN, TFD_NONBLOCK,
+};
+
+/// A safe wrapper around a Linux timerfd (man 2 timerfd_create).
+///
+/// A timerfd behaves like an `EventFd` that the kernel writes to on its own once a timeout (and
+/// optionally a repeating interval) elapses, which lets a timeout be waited on alongside other
+/// fds in a `PollContext` instead of needing a dedicated sleeping thread.
+pub struct TimerFd {
+    timerfd: File,
+}
+
+fn duration_to_timespec(dur: Duration) -> timespec {
+    timespec {
+        tv_sec: dur.as_secs() as i64,
+        tv_nsec: dur.subsec_nanos() as i64,
+    }
+}
+
+impl TimerFd {
+    /// Creates a new non-blocking `TimerFd`. The timer is initially disarmed and must be started
+    /// with `reset`.
+    pub fn new() -> result::Result<TimerFd, io::Error> {
+        // This is safe because timerfd_create merely allocates a timerfd for our process and we
+        // handle the error case.
+        let ret = unsafe { timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK) };
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            // This is safe because we checked ret for success and know the kernel gave us an fd
+            // that we own.
+            Ok(TimerFd {
+                timerfd: unsafe { File::from_raw_fd(ret) },
+            })
+        }
+    }
+
+    /// Arms the timer so that it expires after `dur`, then (if `interval` is given) every
+    /// `interval` thereafter. Replaces any previously armed timeout.
+    pub fn reset(&self, dur: Duration, interval: Option<Duration>) -> result::Result<(), io::Error> {
+        let spec = itimerspec {
+            it_interval: duration_to_timespec(interval.unwrap_or_default()),
+            it_value: duration_to_timespec(dur),
+        };
+        // This is safe because we give a valid fd and timerspec pointer and check the return
+        // value. We don't need the previous timer state so old_value is null.
+        let ret = unsafe { timerfd_settime(self.as_raw_fd(), 0, &spec, std::ptr::null_mut()) };
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Blocks until the timer has expired at least once, then returns the number of expirations
+    /// that have elapsed since the last successful `wait`.
+    ///
+    /// The underlying fd is created with `TFD_NONBLOCK` so that it can be polled elsewhere
+    /// without blocking, so here we poll for readability ourselves whenever the timer hasn't
+    /// expired yet rather than relying on `read` alone to block.
+    pub fn wait(&self) -> result::Result<u64, io::Error> {
+        let mut count: u64 = 0;
+        loop {
+            // This is safe because we made this fd and the pointer we pass can not overflow
+            // because we give the syscall's size parameter properly.
+            let ret = unsafe {
+                read(
+                    self.as_raw_fd(),
+                    &mut count as *mut u64 as *mut c_void,
+                    mem::size_of::<u64>(),
+                )
+            };
+            if ret > 0 {
+                return Ok(count);
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::WouldBlock {
+                return Err(err);
+            }
+
+            let mut pfd = pollfd {
+                fd: self.as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+            };
+            // This is safe because we give a valid pollfd and check the return value. A timeout
+            // of -1 blocks until the fd is readable.
+            let ret = unsafe { poll(&mut pfd, 1, -1) };
+            if ret < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+
+    /// Disarms the timer, preventing any future expirations.
+    pub fn clear(&self) -> result::Result<(), io::Error> {
+        self.reset(Duration::default(), None)
+    }
+}
+
+impl AsRawFd for TimerFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.timerfd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn new() {
+        TimerFd::new().unwrap();
+    }
+
+    #[test]
+    fn one_shot() {
+        let timer = TimerFd::new().unwrap();
+        timer.reset(Duration::from_millis(1), None).unwrap();
+        let count = timer.wait().unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn clear() {
+        let timer = TimerFd::new().unwrap();
+        timer.reset(Duration::from_millis(100), None).unwrap();
+        timer.clear().unwrap();
+    }
+}